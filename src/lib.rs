@@ -13,14 +13,22 @@
 //!     csvpsql [FLAGS] [OPTIONS] --table-name <table-name> [file]
 //!
 //! FLAGS:
+//!         --enum-as-type Emit a `create type ... as enum` statement instead of an inline check constraint
 //!         --help         Prints help information
 //!     -h, --no-header
+//!         --strict-dates Only classify strict ISO-8601/RFC-3339 strings as date/timestamp
+//!         --with-copy    Append a `copy ... from stdin` statement that loads the table
 //!     -V, --version      Prints version information
 //!
 //! OPTIONS:
 //!         --columns <columns>          Override column name. Separated by comma. Use the csv header or letters by default.
+//!         --compression <compression>  Input compression when reading from stdin, inferred from extension for files: none, gz, bz2, zst
 //!     -d, --delimiter <delimiter>       [default: ,]
+//!         --enum-threshold <enum-threshold>   Max distinct values tracked per column before giving up on enum detection [default: 50]
+//!         --execute <execute>          Connect to this Postgres URL and load the table directly
 //!     -n, --null-as <null-as>          Empty string are null by default [default: ]
+//!         --output-format <output-format>    Output format: sql or json-schema [default: sql]
+//!         --primary-key <primary-key>  Force this column as the primary key instead of auto-detecting one
 //!     -t, --table-name <table-name>    File name is used as default
 //!
 //! ARGS:
@@ -39,16 +47,22 @@
 //!);
 //! ```
 
-use chrono::NaiveTime;
+use bzip2::read::BzDecoder;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
 use csv::StringRecord;
+use flate2::read::GzDecoder;
 use itertools::izip;
+use postgres::{Client, NoTls};
+use std::collections::HashSet;
 use std::error::Error;
 use std::fmt;
 use std::fs;
 use std::io;
 use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use structopt::StructOpt;
+use zstd::stream::read::Decoder as ZstdDecoder;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "csvpsql", about = "Parse csv to sql tables.")]
@@ -83,6 +97,123 @@ pub struct Opt {
         help = "File name is used as default"
     )]
     pub table_name: Option<String>,
+
+    #[structopt(
+        long,
+        default_value = "sql",
+        help = "Output format: sql or json-schema"
+    )]
+    pub output_format: OutputFormat,
+
+    #[structopt(
+        long,
+        default_value = "50",
+        help = "Max distinct values tracked per column before giving up on enum detection"
+    )]
+    pub enum_threshold: usize,
+
+    #[structopt(
+        long,
+        help = "Emit a `create type ... as enum` statement instead of an inline check constraint"
+    )]
+    pub enum_as_type: bool,
+
+    #[structopt(
+        long,
+        help = "Force this column as the primary key instead of auto-detecting one"
+    )]
+    pub primary_key: Option<String>,
+
+    #[structopt(
+        long,
+        help = "Only classify strict ISO-8601/RFC-3339 strings as date/timestamp, instead of any string dtparse accepts"
+    )]
+    pub strict_dates: bool,
+
+    #[structopt(
+        long,
+        help = "Input compression when reading from stdin, ignored for files since it is inferred from their extension: none, gz, bz2, zst"
+    )]
+    pub compression: Option<Compression>,
+
+    #[structopt(
+        long,
+        help = "Append a `copy ... from stdin` statement honoring the chosen delimiter and null-as value"
+    )]
+    pub with_copy: bool,
+
+    #[structopt(
+        long,
+        help = "Connect to this Postgres URL, run the generated create table, and stream the csv rows via COPY"
+    )]
+    pub execute: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Sql,
+    JsonSchema,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sql" => Ok(OutputFormat::Sql),
+            "json-schema" => Ok(OutputFormat::JsonSchema),
+            other => Err(format!(
+                "invalid output format '{}', expected 'sql' or 'json-schema'",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Zstd,
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Compression::None),
+            "gz" | "gzip" => Ok(Compression::Gzip),
+            "bz2" | "bzip2" => Ok(Compression::Bzip2),
+            "zst" | "zstd" => Ok(Compression::Zstd),
+            other => Err(format!(
+                "invalid compression '{}', expected 'none', 'gz', 'bz2' or 'zst'",
+                other
+            )),
+        }
+    }
+}
+
+/// File extensions recognized as compression or csv markers, checked
+/// case-insensitively so e.g. `data.csv.GZ` decompresses correctly.
+const RECOGNIZED_EXTENSIONS: [&str; 4] = ["csv", "gz", "bz2", "zst"];
+
+impl Compression {
+    /// Infers compression from a file's extension, defaulting to `None`.
+    fn from_extension(path: &Path) -> Compression {
+        match path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase())
+            .as_deref()
+        {
+            Some("gz") => Compression::Gzip,
+            Some("bz2") => Compression::Bzip2,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
 }
 
 // TODO: Add missing column types
@@ -103,6 +234,117 @@ impl fmt::Display for ColumnType {
     }
 }
 
+/// The Postgres type actually emitted for a column, resolved from a
+/// `ColumnType` plus the per-column `ColumnStats` accumulated over the scan.
+#[derive(Debug, Clone, PartialEq)]
+enum SqlType {
+    Boolean,
+    SmallInt,
+    Integer,
+    BigInt,
+    Numeric(u32, u32),
+    Date,
+    Timestamp,
+    Varchar(usize),
+}
+
+impl fmt::Display for SqlType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SqlType::Boolean => write!(f, "boolean"),
+            SqlType::SmallInt => write!(f, "smallint"),
+            SqlType::Integer => write!(f, "integer"),
+            SqlType::BigInt => write!(f, "bigint"),
+            SqlType::Numeric(precision, scale) => write!(f, "numeric({}, {})", precision, scale),
+            SqlType::Date => write!(f, "date"),
+            SqlType::Timestamp => write!(f, "timestamp"),
+            SqlType::Varchar(length) => write!(f, "varchar({})", length),
+        }
+    }
+}
+
+impl SqlType {
+    /// Maps a `SqlType` to its `(type, format)` pair in JSON Schema (Draft 7).
+    fn json_schema_type(&self) -> (&'static str, Option<&'static str>) {
+        match self {
+            SqlType::Boolean => ("boolean", None),
+            SqlType::SmallInt | SqlType::Integer | SqlType::BigInt => ("integer", None),
+            SqlType::Numeric(_, _) => ("number", None),
+            SqlType::Date => ("string", Some("date")),
+            SqlType::Timestamp => ("string", Some("date-time")),
+            SqlType::Varchar(_) => ("string", None),
+        }
+    }
+}
+
+/// Per-column accumulators gathered over every field of a column, used to
+/// widen a `ColumnType` into its final `SqlType` once the whole column has
+/// been scanned.
+#[derive(Debug, Clone, Default)]
+struct ColumnStats {
+    int_min: Option<i64>,
+    int_max: Option<i64>,
+    max_integer_digits: u32,
+    max_scale: u32,
+    max_length: usize,
+}
+
+/// Splits a numeric field into `(integer digits, fractional digits)`, e.g.
+/// `"-12.340"` -> `(2, 3)`.
+fn digits_and_scale(field: &str) -> (u32, u32) {
+    let field = field.trim_start_matches(['+', '-']);
+    let (integer_part, scale) = match field.split_once('.') {
+        Some((integer_part, fractional_part)) => (integer_part, fractional_part.len() as u32),
+        None => (field, 0),
+    };
+    let integer_digits = integer_part.trim_start_matches('0');
+    let integer_digits = if integer_digits.is_empty() {
+        1
+    } else {
+        integer_digits.len() as u32
+    };
+    (integer_digits, scale)
+}
+
+fn update_stats(stats: &mut ColumnStats, field: &str) {
+    if let Ok(n) = field.parse::<i64>() {
+        stats.int_min = Some(stats.int_min.map_or(n, |m| m.min(n)));
+        stats.int_max = Some(stats.int_max.map_or(n, |m| m.max(n)));
+    }
+    if field.parse::<f64>().is_ok() {
+        let (integer_digits, scale) = digits_and_scale(field);
+        stats.max_integer_digits = stats.max_integer_digits.max(integer_digits);
+        stats.max_scale = stats.max_scale.max(scale);
+    }
+    stats.max_length = stats.max_length.max(field.chars().count());
+}
+
+/// Resolves a column's widened `SqlType` from its coarse `ColumnType` and the
+/// stats accumulated while scanning it.
+fn resolve_sql_type(ctype: &ColumnType, stats: &ColumnStats) -> SqlType {
+    match ctype {
+        ColumnType::Boolean => SqlType::Boolean,
+        ColumnType::Integer => {
+            let min = stats.int_min.unwrap_or(0);
+            let max = stats.int_max.unwrap_or(0);
+            if min >= i16::MIN as i64 && max <= i16::MAX as i64 {
+                SqlType::SmallInt
+            } else if min >= i32::MIN as i64 && max <= i32::MAX as i64 {
+                SqlType::Integer
+            } else {
+                SqlType::BigInt
+            }
+        }
+        ColumnType::Numeric => {
+            let precision = (stats.max_integer_digits + stats.max_scale).max(1);
+            SqlType::Numeric(precision, stats.max_scale)
+        }
+        ColumnType::Date => SqlType::Date,
+        ColumnType::Timestamp => SqlType::Timestamp,
+        ColumnType::Text | ColumnType::Unknown => SqlType::Varchar(stats.max_length.max(1)),
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 enum ColumnConstraint {
     Nullable,
@@ -120,8 +362,9 @@ impl fmt::Display for ColumnConstraint {
 
 struct Column {
     name: String,
-    ctype: ColumnType,
+    ctype: SqlType,
     constraint: ColumnConstraint,
+    enum_values: Option<Vec<String>>,
 }
 
 impl fmt::Display for Column {
@@ -130,48 +373,174 @@ impl fmt::Display for Column {
     }
 }
 
+impl Column {
+    /// The name of the enum type generated for this column when `--enum-as-type` is set.
+    fn enum_type_name(&self, table_name: &str) -> String {
+        format!("{}_{}", table_name, self.name)
+    }
+}
+
+fn sql_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Escapes `"` and `\` so a string can be interpolated into a hand-built JSON document.
+fn json_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
 type Columns = Vec<Column>;
 
 struct Table {
     name: String,
     columns: Columns,
+    enum_as_type: bool,
+    primary_key: Option<String>,
+    unique_columns: Vec<String>,
 }
 
 impl fmt::Display for Table {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.enum_as_type {
+            for column in &self.columns {
+                if let Some(values) = &column.enum_values {
+                    writeln!(
+                        f,
+                        "create type {} as enum ({});",
+                        column.enum_type_name(&self.name),
+                        values.iter().map(|v| sql_quote(v)).collect::<Vec<_>>().join(", ")
+                    )?;
+                }
+            }
+        }
+
+        let mut lines: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| match (&column.enum_values, self.enum_as_type) {
+                (Some(_), true) => format!(
+                    "{} {} {}",
+                    column.name,
+                    column.enum_type_name(&self.name),
+                    column.constraint
+                ),
+                (Some(values), false) => format!(
+                    "{} check ({} in ({}))",
+                    column,
+                    column.name,
+                    values.iter().map(|v| sql_quote(v)).collect::<Vec<_>>().join(", ")
+                ),
+                (None, _) => format!("{}", column),
+            })
+            .collect();
+
+        if let Some(primary_key) = &self.primary_key {
+            lines.push(format!("primary key ({})", primary_key));
+        }
+        for column in &self.unique_columns {
+            lines.push(format!("unique ({})", column));
+        }
+
         writeln!(f, "create table {} (", self.name)?;
-        for column in &self.columns[0..self.columns.len() - 1] {
-            writeln!(f, "    {},", column)?;
+        for (i, line) in lines.iter().enumerate() {
+            let comma = if i + 1 == lines.len() { "" } else { "," };
+            writeln!(f, "    {}{}", line, comma)?;
         }
-        writeln!(f, "    {}", self.columns[self.columns.len() - 1])?;
         writeln!(f, ");")?;
         Ok(())
     }
 }
 
-fn try_parse_date(field: &str) -> Result<ColumnType, dtparse::ParseError> {
-    let (date, _) = dtparse::parse(field)?;
+impl Table {
+    /// Renders this table as a JSON Schema (Draft 7) document, naming the root
+    /// object after the table and listing `NotNull` columns as `required`.
+    fn to_json_schema(&self) -> String {
+        let mut properties = String::new();
+        let mut required = Vec::new();
+        for (i, column) in self.columns.iter().enumerate() {
+            let (json_type, format) = column.ctype.json_schema_type();
+            let comma = if i + 1 == self.columns.len() { "" } else { "," };
+            let name = json_escape(&column.name);
+            match format {
+                Some(format) => properties.push_str(&format!(
+                    "    \"{}\": {{ \"type\": \"{}\", \"format\": \"{}\" }}{}\n",
+                    name, json_type, format, comma
+                )),
+                None => properties.push_str(&format!(
+                    "    \"{}\": {{ \"type\": \"{}\" }}{}\n",
+                    name, json_type, comma
+                )),
+            }
+            if column.constraint == ColumnConstraint::NotNull {
+                required.push(format!("\"{}\"", name));
+            }
+        }
+
+        format!(
+            "{{\n  \"$schema\": \"http://json-schema.org/draft-07/schema#\",\n  \"title\": \"{}\",\n  \"type\": \"object\",\n  \"properties\": {{\n{}  }},\n  \"required\": [{}]\n}}",
+            json_escape(&self.name),
+            properties,
+            required.join(", ")
+        )
+    }
+
+    /// Builds the `copy ... from stdin` statement that loads this table,
+    /// honoring the delimiter and null-as value used to infer its columns.
+    fn to_copy_statement(&self, delimiter: char, null_as: &str, header: bool) -> String {
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| column.name.clone())
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "copy {} ({}) from stdin with (format csv, delimiter '{}', null {}{})",
+            self.name,
+            columns,
+            delimiter,
+            sql_quote(null_as),
+            if header { ", header" } else { "" }
+        )
+    }
+}
+
+/// Only accepts strict ISO-8601/RFC-3339 dates and timestamps (`YYYY-MM-DD` or
+/// `YYYY-MM-DDThh:mm:ss[.sss][+hh:mm|Z]`), unlike `dtparse` which also parses
+/// ambiguous strings like `"12"` as dates.
+fn try_parse_date_strict(field: &str) -> Option<ColumnType> {
+    if NaiveDate::parse_from_str(field, "%Y-%m-%d").is_ok() {
+        return Some(ColumnType::Date);
+    }
+    if DateTime::parse_from_rfc3339(field).is_ok() {
+        return Some(ColumnType::Timestamp);
+    }
+    // Without an offset; `find_type` lowercases fields, so match `t` as well as `T`.
+    if NaiveDateTime::parse_from_str(&field.to_lowercase(), "%Y-%m-%dt%H:%M:%S%.f").is_ok() {
+        return Some(ColumnType::Timestamp);
+    }
+    None
+}
+
+fn try_parse_date(field: &str, strict_dates: bool) -> Option<ColumnType> {
+    if strict_dates {
+        return try_parse_date_strict(field);
+    }
+    let (date, _) = dtparse::parse(field).ok()?;
     if date.time() == NaiveTime::from_hms(0, 0, 0) {
-        Ok(ColumnType::Date)
+        Some(ColumnType::Date)
     } else {
-        Ok(ColumnType::Timestamp)
+        Some(ColumnType::Timestamp)
     }
 }
 
-fn find_type(xfield: &str) -> ColumnType {
+fn find_type(xfield: &str, strict_dates: bool) -> ColumnType {
     let parsed_field = xfield.to_lowercase();
     match parsed_field.as_str() {
         field if field.is_empty() => ColumnType::Unknown,
         "true" | "false" => ColumnType::Boolean,
         field if field.parse::<isize>().is_ok() => ColumnType::Integer,
         field if field.parse::<f64>().is_ok() => ColumnType::Numeric,
-        field => {
-            if let Ok(c) = try_parse_date(field) {
-                c
-            } else {
-                ColumnType::Text
-            }
-        }
+        field => try_parse_date(field, strict_dates).unwrap_or(ColumnType::Text),
     }
 }
 
@@ -183,13 +552,27 @@ fn find_constraint(field: &str, null_as: &str) -> ColumnConstraint {
     }
 }
 
+/// Strips every trailing recognized extension (`.csv`, `.gz`, `.bz2`, `.zst`),
+/// so `sales.csv.gz` yields `sales` rather than `sales.csv`.
+fn strip_recognized_extensions(file: &Path) -> String {
+    let mut stem = file.to_path_buf();
+    loop {
+        match stem.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if RECOGNIZED_EXTENSIONS.contains(&ext.to_lowercase().as_str()) => {
+                stem = PathBuf::from(stem.file_stem().unwrap().to_owned());
+            }
+            _ => break,
+        }
+    }
+    stem.to_str().unwrap().to_owned()
+}
+
 fn get_table_name(table_name: Option<String>, file: Option<PathBuf>) -> String {
-    let table_name = match (&table_name, &file) {
-        (Some(name), _) => name,
-        (None, Some(file)) => file.file_stem().unwrap().to_str().unwrap(),
-        _ => "csvpsql", // cannot happen due to structopt rules
-    };
-    table_name.to_owned()
+    match (&table_name, &file) {
+        (Some(name), _) => name.to_owned(),
+        (None, Some(file)) => strip_recognized_extensions(file),
+        _ => "csvpsql".to_owned(), // cannot happen due to structopt rules
+    }
 }
 
 fn get_column_names(columns: Option<&str>, no_header: bool, header: &StringRecord) -> Vec<String> {
@@ -209,24 +592,176 @@ fn get_column_names(columns: Option<&str>, no_header: bool, header: &StringRecor
 
 fn get_columns(
     column_names: Vec<String>,
-    column_types: Vec<ColumnType>,
+    column_sql_types: Vec<SqlType>,
     column_constraints: Vec<ColumnConstraint>,
+    column_enum_values: Vec<Option<Vec<String>>>,
 ) -> Columns {
-    izip!(column_names, column_types, column_constraints)
-        .map(|(name, ctype, constraint)| Column {
+    izip!(column_names, column_sql_types, column_constraints, column_enum_values)
+        .map(|(name, ctype, constraint, enum_values)| Column {
             name,
             ctype,
             constraint,
+            enum_values,
         })
         .collect()
 }
 
-pub fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
-    // Read from file or stdin
-    let reader: Box<dyn BufRead> = match opt.file.clone() {
+/// Turns the per-column distinct-value sets collected during the scan into
+/// enum candidates: only `Text`/`Integer` columns that stayed within the
+/// threshold (and saw at least one value) are eligible.
+fn get_column_enum_values(
+    column_values: Vec<Option<HashSet<String>>>,
+    column_types: &[ColumnType],
+) -> Vec<Option<Vec<String>>> {
+    column_values
+        .into_iter()
+        .zip(column_types)
+        .map(|(values, ctype)| match (values, ctype) {
+            (Some(values), ColumnType::Text) | (Some(values), ColumnType::Integer)
+                if !values.is_empty() =>
+            {
+                let mut values: Vec<String> = values.into_iter().collect();
+                values.sort();
+                Some(values)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Picks the primary key from the columns that stayed `NotNull` and fully
+/// distinct through the scan (or honors `forced`), and returns the remaining
+/// candidates to mark `unique`. Errors if `forced` does not name an actual
+/// output column, or names one that isn't `NotNull` and fully distinct.
+fn choose_primary_key(
+    column_names: &[String],
+    column_constraints: &[ColumnConstraint],
+    column_distinct: &[bool],
+    forced: Option<&str>,
+) -> Result<(Option<String>, Vec<String>), Box<dyn Error>> {
+    let mut candidates: Vec<String> = izip!(column_names, column_constraints, column_distinct)
+        .filter(|(_, constraint, distinct)| **distinct && **constraint == ColumnConstraint::NotNull)
+        .map(|(name, _, _)| name.clone())
+        .collect();
+
+    let primary_key = match forced {
+        Some(forced) => {
+            let index = column_names.iter().position(|name| name == forced).ok_or_else(|| {
+                Box::<dyn Error>::from(format!(
+                    "--primary-key '{}' is not one of the output columns",
+                    forced
+                ))
+            })?;
+            if column_constraints[index] != ColumnConstraint::NotNull || !column_distinct[index] {
+                return Err(Box::from(format!(
+                    "--primary-key '{}' has null or duplicate values and cannot be a primary key",
+                    forced
+                )));
+            }
+            Some(forced.to_owned())
+        }
+        None if candidates.is_empty() => None,
+        None => Some(candidates.remove(0)),
+    };
+
+    if let Some(primary_key) = &primary_key {
+        candidates.retain(|name| name != primary_key);
+    }
+
+    Ok((primary_key, candidates))
+}
+
+/// Opens `file` (or stdin when `None`), transparently decompressing it
+/// according to `compression`.
+fn open_reader(
+    file: &Option<PathBuf>,
+    compression: Compression,
+) -> Result<Box<dyn BufRead>, Box<dyn Error>> {
+    let reader: Box<dyn BufRead> = match file {
         None => Box::new(BufReader::new(io::stdin())),
-        Some(filename) => Box::new(BufReader::new(fs::File::open(filename).unwrap())),
+        Some(filename) => Box::new(BufReader::new(fs::File::open(filename)?)),
     };
+    let reader: Box<dyn BufRead> = match compression {
+        Compression::None => reader,
+        Compression::Gzip => Box::new(BufReader::new(GzDecoder::new(reader))),
+        Compression::Bzip2 => Box::new(BufReader::new(BzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(ZstdDecoder::new(reader)?)),
+    };
+    Ok(reader)
+}
+
+type ScanResult = (
+    Vec<ColumnType>,
+    Vec<bool>,
+    Vec<Option<HashSet<String>>>,
+    Vec<ColumnStats>,
+    Vec<Option<HashSet<String>>>,
+);
+
+/// Scans every record, accumulating per-column type, nullability, enum
+/// candidates, numeric widening stats and distinctness in one pass. Takes
+/// `records` by value so callers can peek it for emptiness first without
+/// throwing away the row that peek buffers.
+fn scan_columns(
+    records: impl Iterator<Item = Result<StringRecord, csv::Error>>,
+    number_of_columns: usize,
+    strict_dates: bool,
+    null_as: &str,
+    enum_threshold: usize,
+) -> Result<ScanResult, Box<dyn Error>> {
+    let mut column_types = vec![ColumnType::Unknown; number_of_columns];
+    let mut column_has_null = vec![false; number_of_columns];
+    let mut column_values: Vec<Option<HashSet<String>>> =
+        vec![Some(HashSet::new()); number_of_columns];
+    let mut column_stats = vec![ColumnStats::default(); number_of_columns];
+    let mut column_distinct_sets: Vec<Option<HashSet<String>>> =
+        vec![Some(HashSet::new()); number_of_columns];
+
+    for result in records {
+        let record = result?;
+        for (i, field) in record.iter().enumerate() {
+            let field_type = find_type(field, strict_dates);
+            if field_type > column_types[i] {
+                column_types[i] = field_type
+            }
+            if find_constraint(field, null_as) == ColumnConstraint::Nullable {
+                column_has_null[i] = true;
+            }
+            if field != null_as {
+                if let Some(values) = &mut column_values[i] {
+                    values.insert(field.to_owned());
+                    if values.len() > enum_threshold {
+                        column_values[i] = None;
+                    }
+                }
+            }
+            update_stats(&mut column_stats[i], field);
+            if field != null_as {
+                if let Some(values) = &mut column_distinct_sets[i] {
+                    if !values.insert(field.to_owned()) {
+                        column_distinct_sets[i] = None;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok((
+        column_types,
+        column_has_null,
+        column_values,
+        column_stats,
+        column_distinct_sets,
+    ))
+}
+
+pub fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
+    // Read from file or stdin, transparently decompressing if needed
+    let compression = match &opt.file {
+        Some(filename) => Compression::from_extension(filename),
+        None => opt.compression.unwrap_or(Compression::None),
+    };
+    let reader = open_reader(&opt.file, compression)?;
     let mut rdr = csv::ReaderBuilder::new()
         .has_headers(!opt.no_header)
         .delimiter(opt.delimiter as u8)
@@ -234,8 +769,10 @@ pub fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
 
     let number_of_columns = rdr.headers()?.len();
 
-    // Error check
-    if rdr.records().peekable().peek().is_none() {
+    // Error check. Peeking buffers the first record rather than discarding
+    // it, so the scan below (fed this same iterator) still sees it.
+    let mut records = rdr.records().peekable();
+    if records.peek().is_none() {
         return Err(Box::from("csv file has no records."));
     }
     if let Some(names) = &opt.columns {
@@ -247,41 +784,115 @@ pub fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
     }
 
     // Parse csv
-    let mut column_types = vec![ColumnType::Unknown; number_of_columns];
-    let mut column_constraints = vec![ColumnConstraint::Nullable; number_of_columns];
+    let (column_types, column_has_null, column_values, column_stats, column_distinct_sets) =
+        scan_columns(
+            records,
+            number_of_columns,
+            opt.strict_dates,
+            &opt.null_as,
+            opt.enum_threshold,
+        )?;
 
-    for result in rdr.records() {
-        let record = result?;
-        for (i, field) in record.iter().enumerate() {
-            let field_type = find_type(field);
-            if field_type > column_types[i] {
-                column_types[i] = field_type
-            }
-            let field_constraint = find_constraint(field, &opt.null_as);
-            if field_constraint > column_constraints[i] {
-                column_constraints[i] = field_constraint
-            }
-        }
-    }
-
-    let column_types = column_types
+    let column_types: Vec<ColumnType> = column_types
         .iter()
         .map(|x| match x {
             ColumnType::Unknown => ColumnType::Text,
             a => a.clone(),
         })
         .collect();
+    let column_distinct: Vec<bool> = column_distinct_sets.iter().map(Option::is_some).collect();
+    let column_constraints: Vec<ColumnConstraint> = column_has_null
+        .iter()
+        .map(|has_null| {
+            if *has_null {
+                ColumnConstraint::Nullable
+            } else {
+                ColumnConstraint::NotNull
+            }
+        })
+        .collect();
 
     // Create table
+    let column_enum_values = get_column_enum_values(column_values, &column_types);
+    let column_sql_types: Vec<SqlType> = column_types
+        .iter()
+        .zip(&column_stats)
+        .map(|(ctype, stats)| resolve_sql_type(ctype, stats))
+        .collect();
     let column_names = get_column_names(opt.columns.as_deref(), opt.no_header, rdr.headers()?);
-    let columns = get_columns(column_names, column_types, column_constraints);
+    let (primary_key, unique_columns) = choose_primary_key(
+        &column_names,
+        &column_constraints,
+        &column_distinct,
+        opt.primary_key.as_deref(),
+    )?;
+    let columns = get_columns(
+        column_names,
+        column_sql_types,
+        column_constraints,
+        column_enum_values,
+    );
+    let input_file = opt.file.clone();
     let table_name = get_table_name(opt.table_name, opt.file);
     let table = Table {
         name: table_name,
         columns,
+        enum_as_type: opt.enum_as_type,
+        primary_key,
+        unique_columns,
     };
 
-    println!("{}", table);
+    match opt.output_format {
+        OutputFormat::Sql => {
+            println!("{}", table);
+            if opt.with_copy {
+                println!(
+                    "{};",
+                    table.to_copy_statement(opt.delimiter, &opt.null_as, !opt.no_header)
+                );
+            }
+        }
+        OutputFormat::JsonSchema => println!("{}", table.to_json_schema()),
+    }
+
+    if let Some(database_url) = &opt.execute {
+        execute_against_database(
+            database_url,
+            &table,
+            &input_file,
+            opt.delimiter,
+            &opt.null_as,
+            opt.no_header,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Connects to `database_url`, runs the generated `create table`, then
+/// streams `file`'s (decompressed) csv rows to Postgres via the COPY
+/// protocol. Requires a real input file since stdin cannot be read twice.
+fn execute_against_database(
+    database_url: &str,
+    table: &Table,
+    file: &Option<PathBuf>,
+    delimiter: char,
+    null_as: &str,
+    no_header: bool,
+) -> Result<(), Box<dyn Error>> {
+    let file = file
+        .as_ref()
+        .ok_or("--execute requires an input file; stdin cannot be read twice")?;
+
+    let mut client = Client::connect(database_url, NoTls)?;
+    client.batch_execute(&table.to_string())?;
+
+    let compression = Compression::from_extension(file);
+    let mut reader = open_reader(&Some(file.clone()), compression)?;
+    let copy_statement = table.to_copy_statement(delimiter, null_as, !no_header);
+    let mut writer = client.copy_in(copy_statement.as_str())?;
+    io::copy(&mut reader, &mut writer)?;
+    writer.finish()?;
 
     Ok(())
 }
@@ -289,34 +900,273 @@ pub fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
 mod test {
     #[allow(unused)]
     use super::*;
-    #[allow(unused)]
-    use std::path::Path;
 
     #[test]
     fn test_find_type() {
-        assert_eq!(find_type("true"), ColumnType::Boolean);
-        assert_eq!(find_type("false"), ColumnType::Boolean);
-        assert_eq!(find_type("TRUE"), ColumnType::Boolean);
-        assert_eq!(find_type("0"), ColumnType::Integer);
-        assert_eq!(find_type("0.0"), ColumnType::Numeric);
+        assert_eq!(find_type("true", false), ColumnType::Boolean);
+        assert_eq!(find_type("false", false), ColumnType::Boolean);
+        assert_eq!(find_type("TRUE", false), ColumnType::Boolean);
+        assert_eq!(find_type("0", false), ColumnType::Integer);
+        assert_eq!(find_type("0.0", false), ColumnType::Numeric);
     }
 
     #[test]
     fn test_parse_date() {
-        assert_eq!(try_parse_date("2020-01-01"), Ok(ColumnType::Date));
+        assert_eq!(try_parse_date("2020-01-01", false), Some(ColumnType::Date));
         assert_eq!(dtparse::parse(""), Ok(()));
         assert_eq!(
-            try_parse_date("2020-01-01 18:30:04 +02:00"),
-            Ok(ColumnType::Timestamp)
+            try_parse_date("2020-01-01 18:30:04 +02:00", false),
+            Some(ColumnType::Timestamp)
         );
     }
 
+    #[test]
+    fn test_find_type_strict_dates() {
+        assert_eq!(find_type("2020-01-01", true), ColumnType::Date);
+        assert_eq!(
+            find_type("2020-01-01T18:30:04+02:00", true),
+            ColumnType::Timestamp
+        );
+        // Text that is not a date at all stays `Text` in strict mode too.
+        assert_eq!(find_type("not a date", true), ColumnType::Text);
+    }
+
     #[test]
     fn test_find_constraint() {
         assert_eq!(find_constraint("", ""), ColumnConstraint::Nullable);
         assert_eq!(find_constraint("smth", ""), ColumnConstraint::NotNull);
     }
 
+    #[test]
+    fn test_to_json_schema() {
+        let table = Table {
+            name: "example".to_owned(),
+            columns: vec![
+                Column {
+                    name: "id".to_owned(),
+                    ctype: SqlType::Integer,
+                    constraint: ColumnConstraint::NotNull,
+                    enum_values: None,
+                },
+                Column {
+                    name: "created_at".to_owned(),
+                    ctype: SqlType::Timestamp,
+                    constraint: ColumnConstraint::Nullable,
+                    enum_values: None,
+                },
+            ],
+            enum_as_type: false,
+            primary_key: None,
+            unique_columns: Vec::new(),
+        };
+        let schema = table.to_json_schema();
+        assert!(schema.contains("\"title\": \"example\""));
+        assert!(schema.contains("\"id\": { \"type\": \"integer\" }"));
+        assert!(schema.contains("\"created_at\": { \"type\": \"string\", \"format\": \"date-time\" }"));
+        assert!(schema.contains("\"required\": [\"id\"]"));
+    }
+
+    #[test]
+    fn test_to_json_schema_escapes_quotes() {
+        let table = Table {
+            name: "example".to_owned(),
+            columns: vec![Column {
+                name: "say \"hi\"".to_owned(),
+                ctype: SqlType::Varchar(10),
+                constraint: ColumnConstraint::Nullable,
+                enum_values: None,
+            }],
+            enum_as_type: false,
+            primary_key: None,
+            unique_columns: Vec::new(),
+        };
+        let schema = table.to_json_schema();
+        assert!(schema.contains("\"say \\\"hi\\\"\": { \"type\": \"string\" }"));
+    }
+
+    #[test]
+    fn test_to_copy_statement() {
+        let table = Table {
+            name: "example".to_owned(),
+            columns: vec![Column {
+                name: "id".to_owned(),
+                ctype: SqlType::Integer,
+                constraint: ColumnConstraint::NotNull,
+                enum_values: None,
+            }],
+            enum_as_type: false,
+            primary_key: None,
+            unique_columns: Vec::new(),
+        };
+        assert_eq!(
+            table.to_copy_statement(',', "", true),
+            "copy example (id) from stdin with (format csv, delimiter ',', null '', header)"
+        );
+    }
+
+    #[test]
+    fn test_get_column_enum_values() {
+        let column_types = vec![ColumnType::Text, ColumnType::Integer, ColumnType::Numeric];
+        let column_values = vec![
+            Some(["a", "b"].iter().map(|s| s.to_string()).collect()),
+            None,
+            Some(["1.0"].iter().map(|s| s.to_string()).collect()),
+        ];
+        let enum_values = get_column_enum_values(column_values, &column_types);
+        assert_eq!(enum_values[0], Some(vec!["a".to_owned(), "b".to_owned()]));
+        assert_eq!(enum_values[1], None);
+        assert_eq!(enum_values[2], None);
+    }
+
+    #[test]
+    fn test_scan_columns_tracks_enum_values_from_first_record() {
+        // "1" is the first row scanned; it must still land in the enum set.
+        let records = vec![
+            StringRecord::from(vec!["1"]),
+            StringRecord::from(vec!["2"]),
+            StringRecord::from(vec!["3"]),
+        ];
+        let (column_types, _, column_values, _, _) =
+            scan_columns(records.into_iter().map(Ok), 1, false, "", 50).unwrap();
+        let enum_values = get_column_enum_values(column_values, &column_types);
+        assert_eq!(
+            enum_values[0],
+            Some(vec!["1".to_owned(), "2".to_owned(), "3".to_owned()])
+        );
+    }
+
+    #[test]
+    fn test_digits_and_scale() {
+        assert_eq!(digits_and_scale("123"), (3, 0));
+        assert_eq!(digits_and_scale("-12.340"), (2, 3));
+        assert_eq!(digits_and_scale("0.5"), (1, 1));
+    }
+
+    #[test]
+    fn test_scan_columns_widens_from_first_record() {
+        // "99999999999" overflows smallint by orders of magnitude; it must
+        // still be seen even when it's the very first row scanned.
+        let records = vec![
+            StringRecord::from(vec!["99999999999"]),
+            StringRecord::from(vec!["1"]),
+        ];
+        let (column_types, _, _, column_stats, _) =
+            scan_columns(records.into_iter().map(Ok), 1, false, "", 50).unwrap();
+        assert_eq!(
+            resolve_sql_type(&column_types[0], &column_stats[0]),
+            SqlType::BigInt
+        );
+    }
+
+    #[test]
+    fn test_resolve_sql_type() {
+        let mut small = ColumnStats::default();
+        update_stats(&mut small, "42");
+        assert_eq!(resolve_sql_type(&ColumnType::Integer, &small), SqlType::SmallInt);
+
+        let mut big = ColumnStats::default();
+        update_stats(&mut big, "9999999999");
+        assert_eq!(resolve_sql_type(&ColumnType::Integer, &big), SqlType::BigInt);
+
+        let mut numeric = ColumnStats::default();
+        update_stats(&mut numeric, "123.45");
+        assert_eq!(
+            resolve_sql_type(&ColumnType::Numeric, &numeric),
+            SqlType::Numeric(5, 2)
+        );
+
+        let mut text = ColumnStats::default();
+        update_stats(&mut text, "hello");
+        assert_eq!(resolve_sql_type(&ColumnType::Text, &text), SqlType::Varchar(5));
+    }
+
+    #[test]
+    fn test_choose_primary_key() {
+        let column_names = vec!["id".to_owned(), "email".to_owned(), "name".to_owned()];
+        let column_constraints = vec![
+            ColumnConstraint::NotNull,
+            ColumnConstraint::NotNull,
+            ColumnConstraint::Nullable,
+        ];
+        let column_distinct = vec![true, true, true];
+
+        let (primary_key, unique_columns) =
+            choose_primary_key(&column_names, &column_constraints, &column_distinct, None).unwrap();
+        assert_eq!(primary_key, Some("id".to_owned()));
+        assert_eq!(unique_columns, vec!["email".to_owned()]);
+
+        let (primary_key, unique_columns) = choose_primary_key(
+            &column_names,
+            &column_constraints,
+            &column_distinct,
+            Some("email"),
+        )
+        .unwrap();
+        assert_eq!(primary_key, Some("email".to_owned()));
+        assert_eq!(unique_columns, vec!["id".to_owned()]);
+
+        assert!(choose_primary_key(
+            &column_names,
+            &column_constraints,
+            &column_distinct,
+            Some("nonexistent"),
+        )
+        .is_err());
+
+        // "name" is nullable: forcing it must be rejected,
+        // not silently emitted as a broken `primary key`.
+        assert!(choose_primary_key(
+            &column_names,
+            &column_constraints,
+            &column_distinct,
+            Some("name"),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_scan_columns_sees_first_record() {
+        let records = vec![
+            StringRecord::from(vec!["1"]),
+            StringRecord::from(vec!["1"]),
+            StringRecord::from(vec!["2"]),
+        ];
+        let (_, _, _, _, column_distinct_sets) = scan_columns(
+            records.into_iter().map(Ok),
+            1,
+            false,
+            "",
+            50,
+        )
+        .unwrap();
+        // The duplicate "1" spans rows 1-2, so the column must not look distinct.
+        assert!(column_distinct_sets[0].is_none());
+    }
+
+    #[test]
+    fn test_compression_from_extension() {
+        assert_eq!(
+            Compression::from_extension(Path::new("file.csv.gz")),
+            Compression::Gzip
+        );
+        assert_eq!(
+            Compression::from_extension(Path::new("file.csv.bz2")),
+            Compression::Bzip2
+        );
+        assert_eq!(
+            Compression::from_extension(Path::new("file.csv.zst")),
+            Compression::Zstd
+        );
+        assert_eq!(
+            Compression::from_extension(Path::new("file.csv")),
+            Compression::None
+        );
+        assert_eq!(
+            Compression::from_extension(Path::new("file.csv.GZ")),
+            Compression::Gzip
+        );
+    }
+
     #[test]
     fn test_get_table_name() {
         assert_eq!(
@@ -333,5 +1183,12 @@ mod test {
             ),
             "f"
         );
+        assert_eq!(
+            get_table_name(
+                None,
+                Option::from(PathBuf::from(Box::from(Path::new("sales.csv.gz"))))
+            ),
+            "sales"
+        );
     }
 }